@@ -0,0 +1,485 @@
+//! Optional wgpu compute backend for [`encode_img_gpu_inner`]/[`decode_img_gpu_inner`].
+//!
+//! The scalar CPU loop in `encode_img_inner`/`decode_img_inner` reads or
+//! writes one pixel LSB per iteration, which dominates runtime on
+//! multi-megapixel carriers. This module moves that loop onto the GPU via a
+//! compute shader: one invocation per payload bit clears and sets the LSB
+//! of the target channel of that bit's pixel. It currently only accelerates the default (sequential,
+//! non-interleaved, non-keyed) placement, since that's the common case and
+//! the one whose bit-to-pixel mapping is a trivial linear scan; callers that
+//! need interleaving, outer parity, or keyed placement should use the CPU
+//! path, which remains the default and is unaffected by this module.
+//!
+//! Per-bit physical pixel addresses are precomputed host-side with
+//! [`sequential_addrs`], which replicates the CPU sequential path's
+//! `block_region_size = logical_capacity / total_blocks` spacing and
+//! `physical_bit_index` header-anchor skip exactly, so the shader itself
+//! does no placement logic beyond indexing into the precomputed list. This
+//! keeps the two backends byte-compatible: an image encoded with
+//! `encode_img_gpu` decodes correctly with `decode_img`, and vice versa, as
+//! long as the CPU-side caller used the default (sequential) placement.
+
+use crate::{
+    build_header_block, decode_blocks_with_erasures, encode_data_blocks, encode_outer_parity,
+    header_anchor_offsets, logical_capacity, merged_reserved_ranges, physical_bit_index,
+    read_valid_header, recover_erased_blocks, verify_digest, write_header_redundant,
+    EncodeOptions, ECC_CODE_LEN, ECC_DATA_LEN, HASH_LEN, HEADER_BLOCK_LEN,
+};
+#[cfg(test)]
+use crate::{read_block_sequential, write_block_sequential};
+use anyhow::{anyhow, Error};
+use byteorder::{ByteOrder, LittleEndian};
+use image::{DynamicImage, ImageFormat};
+use std::io::Cursor;
+
+const WORKGROUP_SIZE: u32 = 64;
+
+// `addrs[i]` is the physical pixel-slot address (see `crate::physical_bit_index`)
+// that logical payload bit `i` belongs in; it's precomputed host-side by
+// `sequential_addrs`, so the shader has no placement logic of its own -- it
+// only needs to read or write the channel LSB at the given address.
+const WRITE_SHADER: &str = r#"
+@group(0) @binding(0) var<storage, read_write> pixels: array<u32>;
+@group(0) @binding(1) var<storage, read> bits: array<u32>;
+@group(0) @binding(2) var<storage, read> addrs: array<u32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if (i >= arrayLength(&bits)) {
+        return;
+    }
+    let p = addrs[i];
+    let chan = p % 3u;
+    let shift = chan * 8u;
+    let pixel = pixels[p];
+    let byte = (pixel >> shift) & 0xFFu;
+    let new_byte = (byte & 0xFEu) | (bits[i] & 1u);
+    let mask = ~(0xFFu << shift);
+    pixels[p] = (pixel & mask) | (new_byte << shift);
+}
+"#;
+
+const READ_SHADER: &str = r#"
+@group(0) @binding(0) var<storage, read> pixels: array<u32>;
+@group(0) @binding(1) var<storage, read_write> bits_out: array<u32>;
+@group(0) @binding(2) var<storage, read> addrs: array<u32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if (i >= arrayLength(&bits_out)) {
+        return;
+    }
+    let p = addrs[i];
+    let chan = p % 3u;
+    let shift = chan * 8u;
+    let byte = (pixels[p] >> shift) & 0xFFu;
+    bits_out[i] = byte & 1u;
+}
+"#;
+
+struct GpuContext {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+}
+
+async fn gpu_context() -> Result<GpuContext, Error> {
+    let instance = wgpu::Instance::default();
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions::default())
+        .await
+        .ok_or_else(|| anyhow!("no suitable GPU adapter found"))?;
+    let (device, queue) = adapter
+        .request_device(&wgpu::DeviceDescriptor::default(), None)
+        .await
+        .map_err(|e| anyhow!(e))?;
+    Ok(GpuContext { device, queue })
+}
+
+/// Precomputes the physical pixel-slot address of every payload bit, in the
+/// same layout as the CPU sequential path's `write_block_sequential`/
+/// `read_block_sequential`: each block gets its own `block_region_size =
+/// logical_capacity / total_blocks` logical-space region, so two blocks
+/// never collide even when a payload spans more than one.
+fn sequential_addrs(block_lens: &[usize], logical_capacity: u32, reserved: &[(u32, u32)]) -> Vec<u32> {
+    let total_blocks = block_lens.len() as u32;
+    // an empty payload with no outer parity has no blocks at all, in which
+    // case there's nothing to place and the division below is never reached
+    if total_blocks == 0 {
+        return Vec::new();
+    }
+    let block_region_size = logical_capacity / total_blocks;
+    let mut addrs = Vec::with_capacity(block_lens.iter().map(|l| l * 8).sum());
+    for (i, &len) in block_lens.iter().enumerate() {
+        let block_start = (i as u32) * block_region_size;
+        for bit in 0..(len as u32 * 8) {
+            addrs.push(physical_bit_index(block_start + bit, reserved));
+        }
+    }
+    addrs
+}
+
+/// GPU-accelerated equivalent of the sequential-placement path of
+/// `encode_img_inner`. Interleaving, outer parity and keyed placement are
+/// not supported here; the default [`EncodeOptions`] are always used.
+pub async fn encode_img_gpu_inner(carrier: &[u8], input: &[u8]) -> Result<Vec<u8>, Error> {
+    let img = image::io::Reader::with_format(Cursor::new(carrier), ImageFormat::Png)
+        .decode()
+        .map_err(|e| anyhow!(e))?;
+    let mut img = img.into_rgba8();
+    let (width, height) = img.dimensions();
+
+    let data_blocks = encode_data_blocks(input);
+    let parity_blocks = encode_outer_parity(&data_blocks, EncodeOptions::default().parity_blocks)?;
+    let digest = *blake3::hash(input).as_bytes();
+    let header = build_header_block(
+        0,
+        digest,
+        input.len() as u64,
+        0, // interleave_depth: unsupported on the GPU path
+        parity_blocks.len() as u8,
+        0, // placement_mode: sequential
+        0, // nonce: unused without keyed placement
+    );
+
+    let header_bits = (HEADER_BLOCK_LEN * 8) as u32;
+    let anchors = header_anchor_offsets(width, height);
+    let reserved = merged_reserved_ranges(&anchors, header_bits);
+    let logical_capacity = logical_capacity(width, height, &reserved)?;
+
+    let total_bytes = HEADER_BLOCK_LEN
+        + data_blocks
+            .iter()
+            .chain(parity_blocks.iter())
+            .map(|b| b.data().len() + b.ecc().len())
+            .sum::<usize>();
+    if logical_capacity < (total_bytes * 8) as u32 {
+        return Err(anyhow!("image too small!"));
+    }
+
+    write_header_redundant(&mut img, width, &anchors, &header);
+
+    let block_lens: Vec<usize> = data_blocks
+        .iter()
+        .chain(parity_blocks.iter())
+        .map(|b| b.data().len() + b.ecc().len())
+        .collect();
+    let addrs = sequential_addrs(&block_lens, logical_capacity, &reserved);
+    let bytes: Vec<u8> = data_blocks
+        .iter()
+        .chain(parity_blocks.iter())
+        .flat_map(|b| b.data().iter().chain(b.ecc().iter()).copied())
+        .collect();
+
+    gpu_write_bits(&mut img, &bytes, &addrs).await?;
+
+    let img = DynamicImage::ImageRgba8(img);
+    let mut buf = Vec::new();
+    let mut cursor = Cursor::new(&mut buf);
+    img.write_to(&mut cursor, ImageFormat::Png)
+        .map_err(|e| anyhow!(e))?;
+    Ok(buf)
+}
+
+/// GPU-accelerated equivalent of the sequential-placement path of
+/// `decode_img_inner`.
+pub async fn decode_img_gpu_inner(img: &[u8]) -> Result<Vec<u8>, Error> {
+    let img = image::io::Reader::with_format(Cursor::new(img), ImageFormat::Png)
+        .decode()
+        .map_err(|e| anyhow!(e))?;
+    let img = img.into_rgba8();
+    let (width, height) = img.dimensions();
+
+    let decoded_header = read_valid_header(&img, width, height)?;
+    let flags = decoded_header[5];
+    let digest: [u8; HASH_LEN] = decoded_header[6..6 + HASH_LEN].try_into().unwrap();
+    let rest = 6 + HASH_LEN;
+    let data_len =
+        LittleEndian::read_uint(&decoded_header[rest..rest + crate::USIZE_SIZE], crate::USIZE_SIZE) as usize;
+    let parity_block_count = decoded_header[rest + crate::USIZE_SIZE + 2] as usize;
+
+    let num_data_blocks = (data_len + ECC_DATA_LEN - 1) / ECC_DATA_LEN;
+    let total_blocks = num_data_blocks + parity_block_count;
+    let header_bits = (HEADER_BLOCK_LEN * 8) as u32;
+    let anchors = header_anchor_offsets(width, height);
+    let reserved = merged_reserved_ranges(&anchors, header_bits);
+    let logical_capacity = logical_capacity(width, height, &reserved)?;
+
+    let mut data_lens = Vec::with_capacity(num_data_blocks);
+    let mut data_left = data_len;
+    for _ in 0..num_data_blocks {
+        let len = if data_left > ECC_DATA_LEN {
+            ECC_DATA_LEN
+        } else {
+            data_left
+        };
+        data_lens.push(len);
+        data_left -= len;
+    }
+
+    let block_lens: Vec<usize> = (0..total_blocks)
+        .map(|i| data_lens.get(i).copied().unwrap_or(ECC_DATA_LEN) + ECC_CODE_LEN)
+        .collect();
+    let total_payload_bytes: usize = block_lens.iter().sum();
+    let addrs = sequential_addrs(&block_lens, logical_capacity, &reserved);
+
+    let bytes = gpu_read_bits(&img, total_payload_bytes, &addrs).await?;
+    let mut blocks = Vec::with_capacity(total_blocks);
+    let mut offset = 0;
+    for len in block_lens {
+        blocks.push(bytes[offset..offset + len].to_vec());
+        offset += len;
+    }
+
+    let parity_blocks = blocks.split_off(num_data_blocks);
+    let (recovered, failed) = decode_blocks_with_erasures(&blocks);
+
+    let out = if failed.is_empty() {
+        let mut out = Vec::with_capacity(data_len);
+        for block in recovered {
+            out.extend_from_slice(&block.unwrap());
+        }
+        out
+    } else {
+        if parity_block_count == 0 || failed.len() > parity_block_count {
+            return Err(anyhow!(
+                "message data is corrupted: {} block(s) unrecoverable",
+                failed.len()
+            ));
+        }
+        let recovered = recover_erased_blocks(recovered, &failed, &parity_blocks, &data_lens)?;
+        let mut out = Vec::with_capacity(data_len);
+        for block in recovered {
+            out.extend_from_slice(&block);
+        }
+        out
+    };
+
+    verify_digest(flags, &digest, &out, None)?;
+    Ok(out)
+}
+
+/// Uploads the RGBA8 buffer and the packed bit stream to the GPU, dispatches
+/// one invocation per bit to clear-and-set its target pixel's channel LSB at
+/// `addrs[i]`, and reads the modified buffer back into `img`.
+async fn gpu_write_bits(img: &mut image::RgbaImage, bytes: &[u8], addrs: &[u32]) -> Result<(), Error> {
+    let ctx = gpu_context().await?;
+    let pixels: &[u32] = bytemuck::cast_slice(img.as_raw());
+    let bits: Vec<u32> = (0..bytes.len() * 8)
+        .map(|i| ((bytes[i / 8] >> (i % 8)) & 0x1) as u32)
+        .collect();
+
+    let pixel_buf = make_buffer(
+        &ctx.device,
+        pixels,
+        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+    );
+    let bits_buf = make_buffer(&ctx.device, &bits, wgpu::BufferUsages::STORAGE);
+    let addrs_buf = make_buffer(&ctx.device, addrs, wgpu::BufferUsages::STORAGE);
+
+    dispatch(&ctx, WRITE_SHADER, &pixel_buf, &bits_buf, &addrs_buf, bits.len() as u32).await;
+
+    let updated_pixels: Vec<u32> = read_back(&ctx, &pixel_buf, pixels.len()).await?;
+    let updated_bytes: &[u8] = bytemuck::cast_slice(&updated_pixels);
+    let raw: &mut [u8] = img;
+    raw.copy_from_slice(updated_bytes);
+
+    Ok(())
+}
+
+/// Uploads the RGBA8 buffer, dispatches one invocation per bit to read the
+/// target pixel's channel LSB at `addrs[i]`, and reads back `num_bytes`
+/// worth of bits.
+async fn gpu_read_bits(img: &image::RgbaImage, num_bytes: usize, addrs: &[u32]) -> Result<Vec<u8>, Error> {
+    let ctx = gpu_context().await?;
+    let pixels: &[u32] = bytemuck::cast_slice(img.as_raw());
+
+    let pixel_buf = make_buffer(&ctx.device, pixels, wgpu::BufferUsages::STORAGE);
+    let num_bits = num_bytes * 8;
+    let zeros = vec![0u32; num_bits];
+    let bits_buf = make_buffer(
+        &ctx.device,
+        &zeros,
+        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+    );
+    let addrs_buf = make_buffer(&ctx.device, addrs, wgpu::BufferUsages::STORAGE);
+
+    dispatch(&ctx, READ_SHADER, &pixel_buf, &bits_buf, &addrs_buf, num_bits as u32).await;
+
+    let bits: Vec<u32> = read_back(&ctx, &bits_buf, num_bits).await?;
+    let mut out = vec![0u8; num_bytes];
+    for (i, &bit) in bits.iter().enumerate() {
+        out[i / 8] |= (bit as u8 & 0x1) << (i % 8);
+    }
+
+    Ok(out)
+}
+
+fn make_buffer(device: &wgpu::Device, data: &[u32], usage: wgpu::BufferUsages) -> wgpu::Buffer {
+    use wgpu::util::DeviceExt;
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(data),
+        usage,
+    })
+}
+
+async fn dispatch(
+    ctx: &GpuContext,
+    shader_src: &str,
+    pixel_buf: &wgpu::Buffer,
+    bits_buf: &wgpu::Buffer,
+    addrs_buf: &wgpu::Buffer,
+    num_invocations: u32,
+) {
+    let shader = ctx.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(shader_src.into()),
+    });
+    let pipeline = ctx
+        .device
+        .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: None,
+            module: &shader,
+            entry_point: "main",
+        });
+    let bind_group_layout = pipeline.get_bind_group_layout(0);
+    let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: pixel_buf.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: bits_buf.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: addrs_buf.as_entire_binding(),
+            },
+        ],
+    });
+
+    let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        let workgroups = (num_invocations + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+        pass.dispatch_workgroups(workgroups.max(1), 1, 1);
+    }
+    ctx.queue.submit(Some(encoder.finish()));
+}
+
+async fn read_back(ctx: &GpuContext, buf: &wgpu::Buffer, len: usize) -> Result<Vec<u32>, Error> {
+    let size = (len * std::mem::size_of::<u32>()) as u64;
+    let staging = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encoder.copy_buffer_to_buffer(buf, 0, &staging, 0, size);
+    ctx.queue.submit(Some(encoder.finish()));
+
+    let slice = staging.slice(..);
+    let (tx, rx) = futures_channel::oneshot::channel();
+    slice.map_async(wgpu::MapMode::Read, move |res| {
+        let _ = tx.send(res);
+    });
+    ctx.device.poll(wgpu::Maintain::Wait);
+    rx.await
+        .map_err(|e| anyhow!(e))?
+        .map_err(|e| anyhow!("failed to map GPU readback buffer: {:?}", e))?;
+
+    let data = slice.get_mapped_range();
+    let out = bytemuck::cast_slice(&data).to_vec();
+    drop(data);
+    staging.unmap();
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sequential_addrs;
+    use crate::{encode_data_blocks, read_block_sequential, write_block_sequential};
+
+    // reads the bit at pixel-slot address `addr`, matching the channel
+    // selection `write_block_sequential`/`read_block_sequential` use.
+    fn read_bit_at(img: &image::RgbaImage, width: u32, addr: u32) -> u8 {
+        let x = addr % width;
+        let y = addr / width;
+        let chan = (addr % 3) as usize;
+        img.get_pixel(x, y)[chan] & 0x1
+    }
+
+    fn check_addrs_match_cpu_layout(payload_lens: &[usize], reserved: &[(u32, u32)]) {
+        let width = 64;
+        let height = 64;
+        let mut img = image::RgbaImage::new(width, height);
+
+        let blocks: Vec<reed_solomon::Buffer> = payload_lens
+            .iter()
+            .map(|&len| encode_data_blocks(&vec![0xABu8; len]).into_iter().next().unwrap())
+            .collect();
+        let block_lens: Vec<usize> = blocks.iter().map(|b| b.data().len() + b.ecc().len()).collect();
+
+        let total_blocks = blocks.len() as u32;
+        let capacity = width * height - reserved.iter().map(|(s, e)| e - s).sum::<u32>();
+        let block_region_size = capacity / total_blocks;
+        for (i, block) in blocks.iter().enumerate() {
+            write_block_sequential(&mut img, width, block, (i as u32) * block_region_size, reserved);
+        }
+
+        let addrs = sequential_addrs(&block_lens, capacity, reserved);
+
+        let mut offset = 0;
+        for (i, block) in blocks.iter().enumerate() {
+            let len = block_lens[i];
+
+            let bits: Vec<u8> = addrs[offset..offset + len * 8]
+                .iter()
+                .map(|&addr| read_bit_at(&img, width, addr))
+                .collect();
+            let mut from_addrs = vec![0u8; len];
+            for (bit_idx, &v) in bits.iter().enumerate() {
+                from_addrs[bit_idx / 8] |= v << (bit_idx % 8);
+            }
+
+            let expected: Vec<u8> = block.data().iter().chain(block.ecc().iter()).copied().collect();
+            assert_eq!(from_addrs, expected, "block {i} mismatch reading via sequential_addrs");
+
+            let block_start = (i as u32) * block_region_size;
+            let via_cpu_read = read_block_sequential(&img, width, block_start, len, reserved);
+            assert_eq!(from_addrs, via_cpu_read, "block {i}: sequential_addrs diverged from read_block_sequential");
+
+            offset += len * 8;
+        }
+    }
+
+    #[test]
+    fn test_sequential_addrs_matches_cpu_layout_single_block() {
+        check_addrs_match_cpu_layout(&[40], &[]);
+    }
+
+    #[test]
+    fn test_sequential_addrs_matches_cpu_layout_multiple_blocks() {
+        check_addrs_match_cpu_layout(&[40, 30, 50], &[]);
+    }
+
+    #[test]
+    fn test_sequential_addrs_matches_cpu_layout_with_reserved_ranges() {
+        check_addrs_match_cpu_layout(&[40, 30], &[(10, 50), (2000, 2100)]);
+    }
+
+    #[test]
+    fn test_sequential_addrs_empty_blocks_is_empty() {
+        assert_eq!(sequential_addrs(&[], 4096, &[]), Vec::<u32>::new());
+    }
+}
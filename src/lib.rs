@@ -6,12 +6,42 @@ use image::io::Reader;
 use image::{DynamicImage, ImageFormat};
 use std::io::Cursor;
 
+#[cfg(feature = "gpu")]
+mod gpu;
+
 const ECC_BLOCK_LEN: usize = 255;
 // use reed-solomon ECC with k = 32, max 16 bytes corrected
 // may be overkill, can prolly reduce, which will lead to better throughput
 const ECC_CODE_LEN: usize = 32;
 const ECC_DATA_LEN: usize = ECC_BLOCK_LEN - 32;
 const USIZE_SIZE: usize = 8;
+const HASH_LEN: usize = 32;
+const NONCE_LEN: usize = 8;
+
+// selects which of the two BLAKE3 verification modes decode should run
+const FLAG_KEYED: u8 = 0x1;
+
+// selects how codeword bytes are mapped onto image pixels
+const PLACEMENT_SEQUENTIAL: u8 = 0;
+const PLACEMENT_INTERLEAVED: u8 = 1;
+const PLACEMENT_KEYED: u8 = 2;
+
+// identifies the stream as stegasus output and lets decode bail out cleanly
+// on non-stego images instead of returning garbage
+const MAGIC: [u8; 4] = *b"STEG";
+const FORMAT_VERSION: u8 = 1;
+
+// number of well-separated copies of the container header written to the
+// image (see `header_anchor_offsets`); losing any single copy to cropping or
+// damage no longer dooms the whole decode
+const NUM_HEADER_ANCHORS: usize = 5;
+
+// header = magic (4) ++ format version (u8) ++ flags (u8) ++ blake3 digest
+// (32) ++ data_len (u64) ++ interleave_depth (u16) ++ parity_blocks (u8) ++
+// placement_mode (u8) ++ placement nonce (u64), RS-encoded like any other
+// block so a damaged header is itself correctable
+const HEADER_DATA_LEN: usize = 4 + 1 + 1 + HASH_LEN + USIZE_SIZE + 2 + 1 + 1 + NONCE_LEN;
+const HEADER_BLOCK_LEN: usize = HEADER_DATA_LEN + ECC_CODE_LEN;
 
 #[wasm_bindgen]
 pub fn encode_img(carrier: &[u8], input: &[u8]) -> Vec<u8> {
@@ -25,42 +55,229 @@ pub fn decode_img(img: &[u8]) -> Vec<u8> {
     decode_img_inner(img).unwrap()
 }
 
+#[wasm_bindgen]
+pub fn encode_img_keyed(carrier: &[u8], input: &[u8], key: &[u8]) -> Vec<u8> {
+    console_error_panic_hook::set_once();
+    let key: [u8; HASH_LEN] = key.try_into().expect("key must be 32 bytes");
+    encode_img_keyed_inner(carrier, input, &key).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn decode_img_keyed(img: &[u8], key: &[u8]) -> Vec<u8> {
+    console_error_panic_hook::set_once();
+    let key: [u8; HASH_LEN] = key.try_into().expect("key must be 32 bytes");
+    decode_img_keyed_inner(img, &key).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn encode_img_with_key(carrier: &[u8], input: &[u8], key: &[u8]) -> Vec<u8> {
+    console_error_panic_hook::set_once();
+    encode_img_with_key_inner(carrier, input, key).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn decode_img_with_key(img: &[u8], key: &[u8]) -> Vec<u8> {
+    console_error_panic_hook::set_once();
+    decode_img_with_key_inner(img, key).unwrap()
+}
+
+#[cfg(feature = "gpu")]
+#[wasm_bindgen]
+pub fn encode_img_gpu(carrier: Vec<u8>, input: Vec<u8>) -> js_sys::Promise {
+    console_error_panic_hook::set_once();
+    wasm_bindgen_futures::future_to_promise(async move {
+        gpu::encode_img_gpu_inner(&carrier, &input)
+            .await
+            .map(|bytes| js_sys::Uint8Array::from(bytes.as_slice()).into())
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    })
+}
+
+#[cfg(feature = "gpu")]
+#[wasm_bindgen]
+pub fn decode_img_gpu(img: Vec<u8>) -> js_sys::Promise {
+    console_error_panic_hook::set_once();
+    wasm_bindgen_futures::future_to_promise(async move {
+        gpu::decode_img_gpu_inner(&img)
+            .await
+            .map(|bytes| js_sys::Uint8Array::from(bytes.as_slice()).into())
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    })
+}
+
+/// Knobs controlling the optional cross-block interleaving and outer parity
+/// used to survive cropping / localized region damage. Both default to
+/// "off", matching the original sequential, non-redundant block layout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncodeOptions {
+    /// When non-zero, requests that codeword symbols be scattered across
+    /// the whole image instead of written region-by-region, so a
+    /// contiguous burst of damage spanning at most `depth` symbol slots only
+    /// ever clips one symbol per codeword.
+    /// The depth actually used is always widened to at least the total
+    /// number of codewords (data + outer parity blocks) so that no two
+    /// codewords ever claim the same slot; the real depth is written into
+    /// the header, so `interleave_depth` here is only a request.
+    pub interleave_depth: usize,
+    /// Number of outer Reed-Solomon parity blocks computed across the data
+    /// blocks. Losing up to this many whole blocks (e.g. to a crop) is
+    /// still recoverable by treating them as erasures.
+    pub parity_blocks: usize,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions {
+            interleave_depth: 0,
+            parity_blocks: 0,
+        }
+    }
+}
+
 pub fn encode_img_inner(carrier: &[u8], input: &[u8]) -> Result<Vec<u8>, Error> {
+    encode_img_inner_with_options(carrier, input, EncodeOptions::default())
+}
+
+pub fn encode_img_inner_with_options(
+    carrier: &[u8],
+    input: &[u8],
+    opts: EncodeOptions,
+) -> Result<Vec<u8>, Error> {
+    encode_img_inner_full(carrier, input, opts, None, None)
+}
+
+/// Authenticated variant of [`encode_img_inner`]: the embedded digest is a
+/// BLAKE3 keyed hash (MAC) instead of a plain hash, so `decode_img_keyed_inner`
+/// will fail unless called with the same key.
+pub fn encode_img_keyed_inner(
+    carrier: &[u8],
+    input: &[u8],
+    key: &[u8; HASH_LEN],
+) -> Result<Vec<u8>, Error> {
+    encode_img_inner_full(carrier, input, EncodeOptions::default(), Some(key), None)
+}
+
+/// Variant of [`encode_img_inner`] that scatters codeword bytes across the
+/// whole image in a key-derived pseudo-random order instead of writing them
+/// region-by-region. Without `key`, the bits can't be located, which both
+/// hides the payload from simple LSB statistics and spreads it so a small
+/// region of damage no longer wipes one contiguous chunk of the payload.
+pub fn encode_img_with_key_inner(
+    carrier: &[u8],
+    input: &[u8],
+    key: &[u8],
+) -> Result<Vec<u8>, Error> {
+    encode_img_inner_full(carrier, input, EncodeOptions::default(), None, Some(key))
+}
+
+fn encode_img_inner_full(
+    carrier: &[u8],
+    input: &[u8],
+    opts: EncodeOptions,
+    digest_key: Option<&[u8; HASH_LEN]>,
+    placement_key: Option<&[u8]>,
+) -> Result<Vec<u8>, Error> {
     let img = Reader::with_format(Cursor::new(carrier), ImageFormat::Png)
         .decode()
         .map_err(|e| anyhow!(e))?;
     let mut img = img.into_rgba8();
     let (width, height) = img.dimensions();
 
-    let blocks = encode_ecc(input);
+    let data_blocks = encode_data_blocks(input);
+    let parity_blocks = encode_outer_parity(&data_blocks, opts.parity_blocks)?;
+    let total_blocks = data_blocks.len() + parity_blocks.len();
+
+    let interleave_depth = if opts.interleave_depth == 0 {
+        0
+    } else {
+        opts.interleave_depth.max(total_blocks)
+    };
+    if interleave_depth > u16::MAX as usize {
+        return Err(anyhow!(
+            "interleave depth {} exceeds the header's u16 field; reduce interleave_depth or split the payload",
+            interleave_depth
+        ));
+    }
+
+    let (flags, digest) = match digest_key {
+        Some(digest_key) => (FLAG_KEYED, *blake3::keyed_hash(digest_key, input).as_bytes()),
+        None => (0u8, *blake3::hash(input).as_bytes()),
+    };
+
+    let placement_mode = if placement_key.is_some() {
+        PLACEMENT_KEYED
+    } else if interleave_depth != 0 {
+        PLACEMENT_INTERLEAVED
+    } else {
+        PLACEMENT_SEQUENTIAL
+    };
+    let nonce: u64 = if placement_key.is_some() {
+        rand::random()
+    } else {
+        0
+    };
+
+    let header = build_header_block(
+        flags,
+        digest,
+        input.len() as u64,
+        interleave_depth as u16,
+        parity_blocks.len() as u8,
+        placement_mode,
+        nonce,
+    );
+
+    let header_bits = (HEADER_BLOCK_LEN * 8) as u32;
+    let anchors = header_anchor_offsets(width, height);
+    let reserved = merged_reserved_ranges(&anchors, header_bits);
+    let logical_capacity = logical_capacity(width, height, &reserved)?;
 
     // error if image is too small
-    if width * height < (ECC_BLOCK_LEN * blocks.len()) as u32 {
+    let total_bytes = HEADER_BLOCK_LEN
+        + data_blocks
+            .iter()
+            .chain(parity_blocks.iter())
+            .map(|b| b.data().len() + b.ecc().len())
+            .sum::<usize>();
+    if logical_capacity < (total_bytes * 8) as u32 {
         return Err(anyhow!("image too small!"));
     }
 
-    let block_region_size = (width * height) / blocks.len() as u32;
+    write_header_redundant(&mut img, width, &anchors, &header);
 
-    for (i, block) in blocks.iter().enumerate() {
-        let block_start = (i as u32) * block_region_size;
-        let mut chan = 0;
-        for j in 0..block.data().len() * 8 {
-            let x = ((j as u32) + block_start) % width;
-            let y = ((j as u32) + block_start) / width;
-            let pixel = img.get_pixel_mut(x as u32, y as u32);
-            let block_byte = block.data()[j / 8];
-            let bit = (block_byte >> (j % 8)) & 0x1;
-            pixel[chan] = pixel[chan] & 0xFE | bit;
-            chan = (chan + 1) % 3;
-        }
-        for j in 0..block.ecc().len() * 8 {
-            let x = ((j as u32) + block_start + ((block.data().len() * 8) as u32)) % width;
-            let y = ((j as u32) + block_start + ((block.data().len() * 8) as u32)) / width;
-            let pixel = img.get_pixel_mut(x as u32, y as u32);
-            let block_byte = block.ecc()[j / 8];
-            let bit = (block_byte >> (j % 8)) & 0x1;
-            pixel[chan] = pixel[chan] & 0xFE | bit;
-            chan = (chan + 1) % 3;
+    match placement_mode {
+        PLACEMENT_KEYED => {
+            let bytes: Vec<u8> = data_blocks
+                .iter()
+                .chain(parity_blocks.iter())
+                .flat_map(|b| b.data().iter().chain(b.ecc().iter()).copied())
+                .collect();
+            write_bytes_keyed(
+                &mut img,
+                width,
+                &bytes,
+                placement_key.unwrap(),
+                nonce,
+                logical_capacity,
+                &reserved,
+            );
+        }
+        PLACEMENT_INTERLEAVED => {
+            for (c, block) in data_blocks.iter().chain(parity_blocks.iter()).enumerate() {
+                write_block_interleaved(&mut img, width, block, c, interleave_depth, &reserved);
+            }
+        }
+        _ => {
+            // legacy layout: each block gets its own contiguous logical region.
+            // total_blocks is 0 for an empty payload with no outer parity, in
+            // which case there's nothing to write.
+            if total_blocks > 0 {
+                let block_region_size = logical_capacity / total_blocks as u32;
+                for (i, block) in data_blocks.iter().chain(parity_blocks.iter()).enumerate() {
+                    let block_start = (i as u32) * block_region_size;
+                    write_block_sequential(&mut img, width, block, block_start, &reserved);
+                }
+            }
         }
     }
 
@@ -74,73 +291,622 @@ pub fn encode_img_inner(carrier: &[u8], input: &[u8]) -> Result<Vec<u8>, Error>
     Ok(buf)
 }
 
-pub fn decode_img_inner(img: &[u8]) -> Result<Vec<u8>, Error> {
-    let img = Reader::with_format(Cursor::new(img), ImageFormat::Png)
-        .decode()
-        .map_err(|e| anyhow!(e))?;
-    let mut img = img.into_rgba8();
-    let (width, height) = img.dimensions();
+/// Computes the pixel-space bit offsets of the `NUM_HEADER_ANCHORS`
+/// redundant copies of the container header: top-left, top-right,
+/// bottom-left, bottom-right and center. Losing any single one of these
+/// (e.g. to a crop or an overlay) no longer dooms the decode, since
+/// [`read_valid_header`] tries each in turn. Offsets are clamped into range
+/// and deduplicated, so tiny images may end up with fewer than
+/// `NUM_HEADER_ANCHORS` distinct anchors.
+pub(crate) fn header_anchor_offsets(width: u32, height: u32) -> Vec<u32> {
+    let header_bits = (HEADER_BLOCK_LEN * 8) as u32;
+    let total_bits = width * height;
+    let max_start = total_bits.saturating_sub(header_bits);
+    let candidates: [u32; NUM_HEADER_ANCHORS] = [
+        0,                                      // top-left
+        width.saturating_sub(header_bits),      // top-right
+        height.saturating_sub(1) * width,       // bottom-left
+        total_bits.saturating_sub(header_bits), // bottom-right
+        (height / 2) * width + width / 2,       // center
+    ];
+    let mut offsets: Vec<u32> = candidates.iter().map(|&c| c.min(max_start)).collect();
+    offsets.sort_unstable();
+    offsets.dedup();
+    offsets
+}
+
+/// Merges the `[anchor, anchor + header_bits)` ranges reserved for header
+/// copies into a sorted, disjoint list, so overlapping anchors (which can
+/// happen on small images) aren't double-counted.
+pub(crate) fn merged_reserved_ranges(anchors: &[u32], header_bits: u32) -> Vec<(u32, u32)> {
+    let mut ranges: Vec<(u32, u32)> = anchors.iter().map(|&a| (a, a + header_bits)).collect();
+    ranges.sort_unstable();
+    let mut merged: Vec<(u32, u32)> = Vec::with_capacity(ranges.len());
+    for r in ranges {
+        match merged.last_mut() {
+            Some(last) if r.0 <= last.1 => last.1 = last.1.max(r.1),
+            _ => merged.push(r),
+        }
+    }
+    merged
+}
+
+/// Number of bit-slots left for payload once the header anchors' reserved
+/// ranges are excluded. `reserved` is built from `header_anchor_offsets`,
+/// whose candidates are always clamped so that an individual anchor plus
+/// `header_bits` never exceeds `width * height` -- *unless* `header_bits`
+/// itself is larger than `width * height`, i.e. the image is too small to
+/// hold even one header copy. Returns `Err` in that case instead of letting
+/// the subtraction underflow.
+pub(crate) fn logical_capacity(width: u32, height: u32, reserved: &[(u32, u32)]) -> Result<u32, Error> {
+    let reserved_bits = reserved.iter().map(|(s, e)| e - s).sum::<u32>();
+    (width * height)
+        .checked_sub(reserved_bits)
+        .ok_or_else(|| anyhow!("image too small!"))
+}
+
+/// Translates a `logical` bit position in the bulk-payload address space
+/// (which excludes all header anchor ranges) into its physical bit position
+/// in the image, so codeword placement never clobbers a header copy.
+pub(crate) fn physical_bit_index(logical: u32, reserved: &[(u32, u32)]) -> u32 {
+    let mut physical = logical;
+    for &(start, end) in reserved {
+        if physical >= start {
+            physical += end - start;
+        }
+    }
+    physical
+}
+
+/// Writes the RS-encoded container header to every anchor in `anchors`, so
+/// the copy survives as long as at least one anchor does.
+pub(crate) fn write_header_redundant(img: &mut image::RgbaImage, width: u32, anchors: &[u32], header: &reed_solomon::Buffer) {
+    for &anchor in anchors {
+        write_header_bits_at(img, width, header, anchor);
+    }
+}
+
+/// Writes the RS-encoded container header to the `HEADER_BLOCK_LEN` bytes'
+/// worth of pixels starting at bit offset `anchor`.
+fn write_header_bits_at(img: &mut image::RgbaImage, width: u32, header: &reed_solomon::Buffer, anchor: u32) {
+    let header_bytes = header.data();
+    let ecc_bytes = header.ecc();
+    for bit in 0..HEADER_BLOCK_LEN * 8 {
+        let i = anchor + bit as u32;
+        let x = i % width;
+        let y = i / width;
+        let pixel = img.get_pixel_mut(x, y);
+        let chan = (i % 3) as usize;
+        let byte = if bit / 8 < header_bytes.len() {
+            header_bytes[bit / 8]
+        } else {
+            ecc_bytes[bit / 8 - header_bytes.len()]
+        };
+        let v = (byte >> (bit % 8)) & 0x1;
+        pixel[chan] = pixel[chan] & 0xFE | v;
+    }
+}
+
+/// Reads back the raw (still RS-encoded) container header bytes written by
+/// [`write_header_bits_at`] at bit offset `anchor`.
+fn read_header_bits_at(img: &image::RgbaImage, width: u32, anchor: u32) -> Vec<u8> {
+    let mut header_block = vec![0u8; HEADER_BLOCK_LEN];
+    for bit in 0..HEADER_BLOCK_LEN * 8 {
+        let i = anchor + bit as u32;
+        let x = i % width;
+        let y = i / width;
+        let pixel = img.get_pixel(x, y);
+        let chan = (i % 3) as usize;
+        let v = pixel[chan] & 0x1;
+        header_block[bit / 8] |= v << (bit % 8);
+    }
+    header_block
+}
+
+/// Writes the RS-encoded container header to the first `HEADER_BLOCK_LEN`
+/// bytes' worth of pixels, starting at slot 0 (the primary, top-left
+/// anchor). A single-anchor primitive; the CPU and GPU paths both write
+/// every anchor through [`write_header_redundant`] instead.
+pub(crate) fn write_header_bits(img: &mut image::RgbaImage, width: u32, header: &reed_solomon::Buffer) {
+    write_header_bits_at(img, width, header, 0);
+}
+
+/// Reads back the raw (still RS-encoded) container header bytes written by
+/// [`write_header_bits`] at the primary, top-left anchor.
+pub(crate) fn read_header_bits(img: &image::RgbaImage, width: u32) -> Vec<u8> {
+    read_header_bits_at(img, width, 0)
+}
+
+/// Tries every header anchor in turn, returning the decoded bytes of the
+/// first copy that both passes RS correction and carries the expected magic
+/// and format version. Returns an error if none do, which also cleanly
+/// rejects images that were never stego-encoded in the first place.
+pub(crate) fn read_valid_header(img: &image::RgbaImage, width: u32, height: u32) -> Result<Vec<u8>, Error> {
+    for anchor in header_anchor_offsets(width, height) {
+        let raw = read_header_bits_at(img, width, anchor);
+        if let Ok(decoded) = decode_ecc(vec![raw]) {
+            if decoded.len() >= 5 && decoded[0..4] == MAGIC && decoded[4] == FORMAT_VERSION {
+                return Ok(decoded);
+            }
+        }
+    }
+    Err(anyhow!(
+        "no valid stegasus header found: this may not be a steganographically-encoded image, \
+         or every header copy is too damaged to recover"
+    ))
+}
+
+pub(crate) fn write_block_sequential(
+    img: &mut image::RgbaImage,
+    width: u32,
+    block: &reed_solomon::Buffer,
+    block_start: u32,
+    reserved: &[(u32, u32)],
+) {
+    let mut bit = 0u32;
+    for &byte in block.data().iter().chain(block.ecc().iter()) {
+        for b in 0..8 {
+            let i = physical_bit_index(block_start + bit, reserved);
+            let x = i % width;
+            let y = i / width;
+            let pixel = img.get_pixel_mut(x, y);
+            let chan = (i % 3) as usize;
+            let v = (byte >> b) & 0x1;
+            pixel[chan] = pixel[chan] & 0xFE | v;
+            bit += 1;
+        }
+    }
+}
+
+fn write_block_interleaved(
+    img: &mut image::RgbaImage,
+    width: u32,
+    block: &reed_solomon::Buffer,
+    codeword_idx: usize,
+    depth: usize,
+    reserved: &[(u32, u32)],
+) {
+    for (byte_idx, &byte) in block.data().iter().chain(block.ecc().iter()).enumerate() {
+        let slot = interleaved_slot(codeword_idx, byte_idx, depth);
+        for b in 0..8 {
+            let i = physical_bit_index((slot as u32) * 8 + b, reserved);
+            let x = i % width;
+            let y = i / width;
+            let pixel = img.get_pixel_mut(x, y);
+            let chan = (i % 3) as usize;
+            let v = (byte >> b) & 0x1;
+            pixel[chan] = pixel[chan] & 0xFE | v;
+        }
+    }
+}
+
+/// Maps byte `byte_idx` of codeword `codeword_idx` to its slot in the
+/// interleaved symbol stream. Since `depth` is always widened to at least
+/// the total codeword count, codeword `codeword_idx` always owns slots
+/// `codeword_idx, codeword_idx + depth, codeword_idx + 2*depth, ...`, so a
+/// contiguous burst of damage spanning at most `depth` slots can only ever
+/// clip one symbol out of each codeword.
+fn interleaved_slot(codeword_idx: usize, byte_idx: usize, depth: usize) -> usize {
+    byte_idx * depth + codeword_idx
+}
+
+/// Derives a pair of aHash seed keys from an arbitrary-length placement key.
+/// BLAKE3 is only used here to turn a variable-length key into the fixed
+/// 128 bits of seed material aHash wants; the actual pseudo-random stream
+/// used to place bits is aHash, as requested.
+fn placement_seeds(key: &[u8]) -> (u64, u64) {
+    let digest = blake3::hash(key);
+    let bytes = digest.as_bytes();
+    (
+        LittleEndian::read_u64(&bytes[0..8]),
+        LittleEndian::read_u64(&bytes[8..16]),
+    )
+}
+
+/// One value of the keyed PRNG stream: hashes `(nonce, counter)` with an
+/// aHash hasher seeded from the placement key.
+fn keyed_stream_u64(seed0: u64, seed1: u64, nonce: u64, counter: u64) -> u64 {
+    use std::hash::Hasher;
+    let mut hasher = ahash::AHasher::new_with_keys(seed0, seed1);
+    hasher.write_u64(nonce);
+    hasher.write_u64(counter);
+    hasher.finish()
+}
+
+/// Fisher-Yates shuffle of `0..total`, driven by the keyed PRNG stream, used
+/// to scatter bit slots uniformly across the whole image instead of
+/// sequential regions.
+fn keyed_permutation(key: &[u8], nonce: u64, total: usize) -> Vec<usize> {
+    let (seed0, seed1) = placement_seeds(key);
+    let mut perm: Vec<usize> = (0..total).collect();
+    for i in (1..total).rev() {
+        let r = keyed_stream_u64(seed0, seed1, nonce, i as u64);
+        let j = (r as usize) % (i + 1);
+        perm.swap(i, j);
+    }
+    perm
+}
 
-    // get len block
-    let mut chan = 0;
-    let mut block = vec![0; USIZE_SIZE + ECC_CODE_LEN];
+/// Writes `bytes` one bit at a time into the logical slots not reserved for
+/// a header anchor, assigning bit `i` to the key-derived permutation's
+/// `i`-th slot rather than to sequential pixels.
+fn write_bytes_keyed(
+    img: &mut image::RgbaImage,
+    width: u32,
+    bytes: &[u8],
+    key: &[u8],
+    nonce: u64,
+    logical_capacity: u32,
+    reserved: &[(u32, u32)],
+) {
+    let perm = keyed_permutation(key, nonce, logical_capacity as usize);
+    for (bit_idx, bit) in bit_indices(bytes) {
+        let slot = perm[bit_idx];
+        let i = physical_bit_index(slot as u32, reserved);
+        let x = i % width;
+        let y = i / width;
+        let pixel = img.get_pixel_mut(x, y);
+        let chan = (i % 3) as usize;
+        pixel[chan] = pixel[chan] & 0xFE | bit;
+    }
+}
 
-    for i in 0..(USIZE_SIZE + ECC_CODE_LEN) * 8 {
-        let x = (i as u32) % width;
-        let y = (i as u32) / width;
+fn read_bytes_keyed(
+    img: &image::RgbaImage,
+    width: u32,
+    num_bytes: usize,
+    key: &[u8],
+    nonce: u64,
+    logical_capacity: u32,
+    reserved: &[(u32, u32)],
+) -> Vec<u8> {
+    let perm = keyed_permutation(key, nonce, logical_capacity as usize);
+    let mut out = vec![0u8; num_bytes];
+    for bit_idx in 0..num_bytes * 8 {
+        let slot = perm[bit_idx];
+        let i = physical_bit_index(slot as u32, reserved);
+        let x = i % width;
+        let y = i / width;
         let pixel = img.get_pixel(x, y);
+        let chan = (i % 3) as usize;
         let bit = pixel[chan] & 0x1;
-        block[i / 8] |= (bit << (i % 8)) as u8;
-        chan = (chan + 1) % 3;
+        out[bit_idx / 8] |= bit << (bit_idx % 8);
     }
-    // println!("len block: {:X?}", block);
-    let decoded_len_block = decode_ecc(vec![block])?;
+    out
+}
+
+/// Iterates `(global_bit_index, bit_value)` pairs over `bytes`, LSB first.
+fn bit_indices(bytes: &[u8]) -> impl Iterator<Item = (usize, u8)> + '_ {
+    bytes
+        .iter()
+        .enumerate()
+        .flat_map(|(byte_idx, &byte)| (0..8).map(move |b| (byte_idx * 8 + b, (byte >> b) & 0x1)))
+}
+
+pub fn decode_img_inner(img: &[u8]) -> Result<Vec<u8>, Error> {
+    decode_img_inner_full(img, None, None)
+}
+
+/// Authenticated variant of [`decode_img_inner`]: verifies the embedded
+/// digest as a BLAKE3 keyed hash (MAC) under `key` instead of a plain hash,
+/// failing unless the caller supplies the key the image was encoded with.
+pub fn decode_img_keyed_inner(img: &[u8], key: &[u8; HASH_LEN]) -> Result<Vec<u8>, Error> {
+    decode_img_inner_full(img, Some(key), None)
+}
 
-    let data_len_bytes = &decoded_len_block[0..USIZE_SIZE];
-    let data_len = LittleEndian::read_uint(data_len_bytes, USIZE_SIZE) as usize;
+/// Variant of [`decode_img_inner`] for images encoded with
+/// [`encode_img_with_key_inner`]; `key` must match the one used at encode
+/// time or the recovered bytes (and therefore the digest check) will be
+/// garbage.
+pub fn decode_img_with_key_inner(img: &[u8], key: &[u8]) -> Result<Vec<u8>, Error> {
+    decode_img_inner_full(img, None, Some(key))
+}
+
+fn decode_img_inner_full(
+    img: &[u8],
+    digest_key: Option<&[u8; HASH_LEN]>,
+    placement_key: Option<&[u8]>,
+) -> Result<Vec<u8>, Error> {
+    let img = Reader::with_format(Cursor::new(img), ImageFormat::Png)
+        .decode()
+        .map_err(|e| anyhow!(e))?;
+    let mut img = img.into_rgba8();
+    let (width, height) = img.dimensions();
 
-    let num_blocks = (data_len + ECC_DATA_LEN - 1) / ECC_DATA_LEN;
+    let decoded_header = read_valid_header(&img, width, height)?;
+    let flags = decoded_header[5];
+    let digest: [u8; HASH_LEN] = decoded_header[6..6 + HASH_LEN].try_into().unwrap();
+    let rest = 6 + HASH_LEN;
+    let data_len = LittleEndian::read_uint(&decoded_header[rest..rest + USIZE_SIZE], USIZE_SIZE) as usize;
+    let interleave_depth =
+        LittleEndian::read_u16(&decoded_header[rest + USIZE_SIZE..rest + USIZE_SIZE + 2]) as usize;
+    let parity_block_count = decoded_header[rest + USIZE_SIZE + 2] as usize;
+    let placement_mode = decoded_header[rest + USIZE_SIZE + 3];
+    let nonce_offset = rest + USIZE_SIZE + 4;
+    let nonce = LittleEndian::read_u64(&decoded_header[nonce_offset..nonce_offset + NONCE_LEN]);
 
-    // block regions determined by num_blocks + 1, not num_blocks bc data len block
-    let block_region_size = width * height / (num_blocks + 1) as u32;
+    let num_data_blocks = (data_len + ECC_DATA_LEN - 1) / ECC_DATA_LEN;
+    let total_blocks = num_data_blocks + parity_block_count;
+    let header_bits = (HEADER_BLOCK_LEN * 8) as u32;
+    let anchors = header_anchor_offsets(width, height);
+    let reserved = merged_reserved_ranges(&anchors, header_bits);
+    let logical_capacity = logical_capacity(width, height, &reserved)?;
 
-    // get blocks
-    let mut blocks = Vec::with_capacity(num_blocks);
+    let mut data_lens = Vec::with_capacity(num_data_blocks);
     let mut data_left = data_len;
-    for i in 1..num_blocks + 1 {
-        let block_start = (i as u32) * block_region_size;
-        let block_data_len = if data_left > ECC_DATA_LEN {
+    for _ in 0..num_data_blocks {
+        let len = if data_left > ECC_DATA_LEN {
             ECC_DATA_LEN
         } else {
             data_left
         };
-        let mut block = vec![0; block_data_len + ECC_CODE_LEN];
-        let mut chan = 0;
-        for j in 0..(block_data_len + ECC_CODE_LEN) * 8 {
-            let x = ((j as u32) + block_start) % width;
-            let y = ((j as u32) + block_start) / width;
-            let pixel = img.get_pixel_mut(x as u32, y as u32);
-            let bit = (pixel[chan] & 0x1) as u8;
-            block[j / 8] |= bit << (j % 8);
-            chan = (chan + 1) % 3;
+        data_lens.push(len);
+        data_left -= len;
+    }
+
+    let mut blocks = Vec::with_capacity(total_blocks);
+    match placement_mode {
+        PLACEMENT_KEYED => {
+            let key = placement_key.ok_or_else(|| {
+                anyhow!("payload uses keyed placement; decode_img_with_key with the correct key is required")
+            })?;
+            let block_lens: Vec<usize> = (0..total_blocks)
+                .map(|i| data_lens.get(i).copied().unwrap_or(ECC_DATA_LEN) + ECC_CODE_LEN)
+                .collect();
+            let total_payload_bytes: usize = block_lens.iter().sum();
+            let bytes = read_bytes_keyed(&img, width, total_payload_bytes, key, nonce, logical_capacity, &reserved);
+            let mut offset = 0;
+            for len in block_lens {
+                blocks.push(bytes[offset..offset + len].to_vec());
+                offset += len;
+            }
+        }
+        PLACEMENT_INTERLEAVED => {
+            for c in 0..total_blocks {
+                let block_data_len = *data_lens.get(c).unwrap_or(&ECC_DATA_LEN);
+                blocks.push(read_block_interleaved(
+                    &img,
+                    width,
+                    c,
+                    interleave_depth,
+                    &reserved,
+                    block_data_len + ECC_CODE_LEN,
+                ));
+            }
+        }
+        _ => {
+            // total_blocks is 0 for an empty payload with no outer parity,
+            // in which case there's nothing to read.
+            let block_region_size = if total_blocks > 0 {
+                logical_capacity / total_blocks as u32
+            } else {
+                0
+            };
+            for i in 0..total_blocks {
+                let block_start = (i as u32) * block_region_size;
+                let block_data_len = *data_lens.get(i).unwrap_or(&ECC_DATA_LEN);
+                blocks.push(read_block_sequential(
+                    &img,
+                    width,
+                    block_start,
+                    block_data_len + ECC_CODE_LEN,
+                    &reserved,
+                ));
+            }
+        }
+    }
+
+    let parity_blocks = blocks.split_off(num_data_blocks);
+    let (recovered, failed) = decode_blocks_with_erasures(&blocks);
+
+    let out = if failed.is_empty() {
+        let mut out = Vec::with_capacity(data_len);
+        for block in recovered {
+            out.extend_from_slice(&block.unwrap());
+        }
+        out
+    } else {
+        if parity_block_count == 0 || failed.len() > parity_block_count {
+            return Err(anyhow!(
+                "message data is corrupted: {} block(s) unrecoverable",
+                failed.len()
+            ));
+        }
+
+        let recovered = recover_erased_blocks(recovered, &failed, &parity_blocks, &data_lens)?;
+        let mut out = Vec::with_capacity(data_len);
+        for block in recovered {
+            out.extend_from_slice(&block);
+        }
+        out
+    };
+
+    verify_digest(flags, &digest, &out, digest_key)?;
+    Ok(out)
+}
+
+/// Recomputes the embedded BLAKE3 digest over the recovered payload and
+/// rejects the decode if it doesn't match — catching the RS-miscorrection
+/// and non-stego-image cases that would otherwise return silent garbage.
+pub(crate) fn verify_digest(
+    flags: u8,
+    expected: &[u8; HASH_LEN],
+    data: &[u8],
+    key: Option<&[u8; HASH_LEN]>,
+) -> Result<(), Error> {
+    let actual = if flags & FLAG_KEYED != 0 {
+        let key = key.ok_or_else(|| anyhow!("payload is keyed; decode_img_keyed with the correct key is required"))?;
+        *blake3::keyed_hash(key, data).as_bytes()
+    } else {
+        *blake3::hash(data).as_bytes()
+    };
+
+    if &actual != expected {
+        return Err(anyhow!("integrity check failed: decoded payload does not match embedded digest"));
+    }
+    Ok(())
+}
+
+pub(crate) fn read_block_sequential(
+    img: &image::RgbaImage,
+    width: u32,
+    block_start: u32,
+    block_len: usize,
+    reserved: &[(u32, u32)],
+) -> Vec<u8> {
+    let mut block = vec![0u8; block_len];
+    for bit in 0..block_len * 8 {
+        let i = physical_bit_index(block_start + bit as u32, reserved);
+        let x = i % width;
+        let y = i / width;
+        let pixel = img.get_pixel(x, y);
+        let chan = (i % 3) as usize;
+        let v = pixel[chan] & 0x1;
+        block[bit / 8] |= v << (bit % 8);
+    }
+    block
+}
+
+fn read_block_interleaved(
+    img: &image::RgbaImage,
+    width: u32,
+    codeword_idx: usize,
+    depth: usize,
+    reserved: &[(u32, u32)],
+    block_len: usize,
+) -> Vec<u8> {
+    let mut block = vec![0u8; block_len];
+    for byte_idx in 0..block_len {
+        let slot = interleaved_slot(codeword_idx, byte_idx, depth);
+        for b in 0..8 {
+            let i = physical_bit_index((slot as u32) * 8 + b, reserved);
+            let x = i % width;
+            let y = i / width;
+            let pixel = img.get_pixel(x, y);
+            let chan = (i % 3) as usize;
+            let v = pixel[chan] & 0x1;
+            block[byte_idx] |= v << b;
+        }
+    }
+    block
+}
+
+/// Tries to inner-RS-correct each block independently. Returns, for every
+/// block, `Some(data)` on success or `None` on failure, plus the indices
+/// that failed so the caller can attempt outer-code erasure recovery.
+pub(crate) fn decode_blocks_with_erasures(blocks: &[Vec<u8>]) -> (Vec<Option<Vec<u8>>>, Vec<usize>) {
+    let decoder = reed_solomon::Decoder::new(ECC_CODE_LEN);
+    let mut out = Vec::with_capacity(blocks.len());
+    let mut failed = Vec::new();
+    for (i, block) in blocks.iter().enumerate() {
+        match decoder.correct(block, None) {
+            Ok(decoded) => out.push(Some(decoded.data().to_vec())),
+            Err(_) => {
+                out.push(None);
+                failed.push(i);
+            }
+        }
+    }
+    (out, failed)
+}
+
+/// Reconstructs data blocks that failed inner correction using the outer
+/// Reed-Solomon code computed across columns of data-region bytes. Parity
+/// blocks sit in the same crop-vulnerable contiguous layout as data blocks,
+/// so a parity block that fails its own inner correction is treated as an
+/// outer-code erasure too, rather than aborting recovery outright -- the
+/// outer code doesn't care whether an erased symbol belonged to a data or
+/// parity block, only that the combined erasure count stays within budget.
+pub(crate) fn recover_erased_blocks(
+    mut recovered: Vec<Option<Vec<u8>>>,
+    failed_data_indices: &[usize],
+    parity_blocks: &[Vec<u8>],
+    data_lens: &[usize],
+) -> Result<Vec<Vec<u8>>, Error> {
+    let num_data_blocks = recovered.len();
+    let outer_decoder = reed_solomon::Decoder::new(parity_blocks.len());
+
+    let inner_decoder = reed_solomon::Decoder::new(ECC_CODE_LEN);
+    let mut parity_data = Vec::with_capacity(parity_blocks.len());
+    let mut failed_parity_indices = Vec::new();
+    for (p, block) in parity_blocks.iter().enumerate() {
+        match inner_decoder.correct(block, None) {
+            Ok(decoded) => parity_data.push(decoded.data().to_vec()),
+            Err(_) => {
+                parity_data.push(vec![0u8; ECC_DATA_LEN]);
+                failed_parity_indices.push(num_data_blocks + p);
+            }
+        }
+    }
+
+    let mut erasure_positions: Vec<u8> = failed_data_indices.iter().map(|&i| i as u8).collect();
+    erasure_positions.extend(failed_parity_indices.iter().map(|&i| i as u8));
+    if erasure_positions.len() > parity_blocks.len() {
+        return Err(anyhow!(
+            "message data is corrupted: {} block(s) unrecoverable",
+            erasure_positions.len()
+        ));
+    }
+
+    for col in 0..ECC_DATA_LEN {
+        let mut column = Vec::with_capacity(num_data_blocks + parity_blocks.len());
+        for i in 0..num_data_blocks {
+            let byte = match &recovered[i] {
+                Some(data) => *data.get(col).unwrap_or(&0),
+                None => 0,
+            };
+            column.push(byte);
+        }
+        for p in &parity_data {
+            column.push(*p.get(col).unwrap_or(&0));
+        }
+
+        let corrected = outer_decoder
+            .correct(&column, Some(&erasure_positions))
+            .map_err(|e| anyhow!(format!("outer code could not recover block(s): {:?}", e)))?;
+
+        for &i in failed_data_indices {
+            let entry = recovered[i].get_or_insert_with(|| vec![0u8; data_lens[i]]);
+            if col < entry.len() {
+                entry[col] = corrected.data()[i];
+            }
         }
-        blocks.push(block);
-        data_left -= block_data_len;
     }
 
-    let decoded = decode_ecc(blocks)?;
-    Ok(decoded)
+    Ok(recovered.into_iter().map(|b| b.unwrap()).collect())
 }
 
-/// encodes input data using reed-solomon block ECC
-/// returns a vector of encoded blocks with an extra block at the beginning saying how long the
-/// data is
-fn encode_ecc(input: &[u8]) -> Vec<reed_solomon::Buffer> {
+pub(crate) fn build_header_block(
+    flags: u8,
+    digest: [u8; HASH_LEN],
+    data_len: u64,
+    interleave_depth: u16,
+    parity_blocks: u8,
+    placement_mode: u8,
+    nonce: u64,
+) -> reed_solomon::Buffer {
+    let mut bytes = [0u8; HEADER_DATA_LEN];
+    bytes[0..4].copy_from_slice(&MAGIC);
+    bytes[4] = FORMAT_VERSION;
+    bytes[5] = flags;
+    bytes[6..6 + HASH_LEN].copy_from_slice(&digest);
+    let rest = 6 + HASH_LEN;
+    bytes[rest..rest + USIZE_SIZE].copy_from_slice(&data_len.to_le_bytes());
+    bytes[rest + USIZE_SIZE..rest + USIZE_SIZE + 2].copy_from_slice(&interleave_depth.to_le_bytes());
+    bytes[rest + USIZE_SIZE + 2] = parity_blocks;
+    bytes[rest + USIZE_SIZE + 3] = placement_mode;
+    let nonce_offset = rest + USIZE_SIZE + 4;
+    bytes[nonce_offset..nonce_offset + NONCE_LEN].copy_from_slice(&nonce.to_le_bytes());
+    reed_solomon::Encoder::new(ECC_CODE_LEN).encode(&bytes)
+}
+
+/// encodes input data into reed-solomon blocks, one codeword per
+/// `ECC_DATA_LEN`-sized chunk of `input`
+pub(crate) fn encode_data_blocks(input: &[u8]) -> Vec<reed_solomon::Buffer> {
     let num_blocks = (input.len() + ECC_DATA_LEN - 1) / ECC_DATA_LEN;
     let encoder = reed_solomon::Encoder::new(ECC_CODE_LEN);
 
-    let mut blocks: Vec<reed_solomon::Buffer> = Vec::with_capacity(num_blocks + 1);
-    blocks.push(encoder.encode(&(input.len() as u64).to_le_bytes()));
+    let mut blocks: Vec<reed_solomon::Buffer> = Vec::with_capacity(num_blocks);
     for i in 0..num_blocks {
         let offset = i * ECC_DATA_LEN;
         if input.len() - offset < ECC_DATA_LEN {
@@ -152,7 +918,54 @@ fn encode_ecc(input: &[u8]) -> Vec<reed_solomon::Buffer> {
     blocks
 }
 
-fn decode_ecc(blocks: Vec<Vec<u8>>) -> Result<Vec<u8>, Error> {
+/// computes `num_parity` outer RS parity blocks across `data_blocks`: for
+/// each column of data-region bytes, runs a second RS encode over the
+/// column (treating each data block as one symbol) and collects the
+/// resulting parity symbols into `num_parity` new, independently
+/// inner-RS-encoded blocks. Losing up to `num_parity` whole data blocks is
+/// then recoverable as an erasure against these parity blocks.
+///
+/// Errs if `data_blocks.len() + num_parity` would exceed
+/// `ECC_BLOCK_LEN` (255), the symbol-count limit of the outer RS codeword,
+/// rather than panicking inside the `reed_solomon` crate.
+pub(crate) fn encode_outer_parity(
+    data_blocks: &[reed_solomon::Buffer],
+    num_parity: usize,
+) -> Result<Vec<reed_solomon::Buffer>, Error> {
+    if num_parity == 0 || data_blocks.is_empty() {
+        return Ok(Vec::new());
+    }
+    if data_blocks.len() + num_parity > ECC_BLOCK_LEN {
+        return Err(anyhow!(
+            "too many blocks for outer parity: {} data block(s) + {} parity block(s) exceeds the {}-symbol outer codeword limit; reduce parity_blocks or split the payload",
+            data_blocks.len(),
+            num_parity,
+            ECC_BLOCK_LEN
+        ));
+    }
+
+    let outer_encoder = reed_solomon::Encoder::new(num_parity);
+    let mut parity_data = vec![vec![0u8; ECC_DATA_LEN]; num_parity];
+
+    for col in 0..ECC_DATA_LEN {
+        let column: Vec<u8> = data_blocks
+            .iter()
+            .map(|b| *b.data().get(col).unwrap_or(&0))
+            .collect();
+        let encoded = outer_encoder.encode(&column);
+        for (p, &byte) in encoded.ecc().iter().enumerate() {
+            parity_data[p][col] = byte;
+        }
+    }
+
+    let inner_encoder = reed_solomon::Encoder::new(ECC_CODE_LEN);
+    Ok(parity_data
+        .into_iter()
+        .map(|data| inner_encoder.encode(&data))
+        .collect())
+}
+
+pub(crate) fn decode_ecc(blocks: Vec<Vec<u8>>) -> Result<Vec<u8>, Error> {
     let decoder = reed_solomon::Decoder::new(ECC_CODE_LEN);
     let mut buf = Vec::new();
     for block in blocks.into_iter() {
@@ -166,18 +979,26 @@ fn decode_ecc(blocks: Vec<Vec<u8>>) -> Result<Vec<u8>, Error> {
 
 #[cfg(test)]
 mod tests {
-    use super::{decode_img_inner, encode_img_inner};
+    use super::{
+        decode_img_inner, decode_img_keyed_inner, decode_img_with_key_inner, encode_img_inner,
+        encode_img_inner_with_options, encode_img_keyed_inner, encode_img_with_key_inner,
+        header_anchor_offsets, logical_capacity, merged_reserved_ranges, physical_bit_index,
+        EncodeOptions, HEADER_BLOCK_LEN,
+    };
     use std::fs::File;
     use std::io::prelude::*;
 
-    fn test_sporkmarmot(data: &[u8]) {
+    fn read_sporkmarmot() -> Vec<u8> {
         let mut buf = Vec::new();
         let mut f = File::open("./pkg/examples/sporkmarmot_riding_bufficorn.png").unwrap();
         f.read_to_end(&mut buf).unwrap();
+        buf
+    }
 
+    fn test_sporkmarmot(data: &[u8]) {
+        let buf = read_sporkmarmot();
         let steg = encode_img_inner(&buf, data).unwrap();
         let decoded = decode_img_inner(&steg).unwrap();
-
         assert_eq!(&data[..], decoded);
     }
 
@@ -198,4 +1019,212 @@ mod tests {
         let data = b"Governments of the Industrial World, you weary giants of flesh and steel, I come from Cyberspace, the new home of Mind. On behalf of the future, I ask you of the past to leave us alone. You are not welcome among us. You have no sovereignty where we gather.\r\n\r\nWe have no elected government, nor are we likely to have one, so I address you with no greater authority than that with which liberty itself always speaks. I declare the global social space we are building to be naturally independent of the tyrannies you seek to impose on us. You have no moral right to rule us nor do you possess any methods of enforcement we have true reason to fear.\r\n\r\nGovernments derive their just powers from the consent of the governed. You have neither solicited nor received ours. We did not invite you. You do not know us, nor do you know our world. Cyberspace does not lie within your borders. Do not think that you can build it, as though it were a public construction project. You cannot. It is an act of nature and it grows itself through our collective actions.\r\n\r\nYou have not engaged in our great and gathering conversation, nor did you create the wealth of our marketplaces. You do not know our culture, our ethics, or the unwritten codes that already provide our society more order than could be obtained by any of your impositions.\r\n\r\nYou claim there are problems among us that you need to solve. You use this claim as an excuse to invade our precincts. Many of these problems don\'t exist. Where there are real conflicts, where there are wrongs, we will identify them and address them by our means. We are forming our own Social Contract. This governance will arise according to the conditions of our world, not yours. Our world is different.\r\n\r\nCyberspace consists of transactions, relationships, and thought itself, arrayed like a standing wave in the web of our communications. Ours is a world that is both everywhere and nowhere, but it is not where bodies live.\r\n\r\nWe are creating a world that all may enter without privilege or prejudice accorded by race, economic power, military force, or station of birth.\r\n\r\nWe are creating a world where anyone, anywhere may express his or her beliefs, no matter how singular, without fear of being coerced into silence or conformity.\r\n\r\nYour legal concepts of property, expression, identity, movement, and context do not apply to us. They are all based on matter, and there is no matter here.\r\n\r\nOur identities have no bodies, so, unlike you, we cannot obtain order by physical coercion. We believe that from ethics, enlightened self-interest, and the commonweal, our governance will emerge. Our identities may be distributed across many of your jurisdictions. The only law that all our constituent cultures would generally recognize is the Golden Rule. We hope we will be able to build our particular solutions on that basis. But we cannot accept the solutions you are attempting to impose.\r\n\r\nIn the United States, you have today created a law, the Telecommunications Reform Act, which repudiates your own Constitution and insults the dreams of Jefferson, Washington, Mill, Madison, DeToqueville, and Brandeis. These dreams must now be born anew in us.\r\n\r\nYou are terrified of your own children, since they are natives in a world where you will always be immigrants. Because you fear them, you entrust your bureaucracies with the parental responsibilities you are too cowardly to confront yourselves. In our world, all the sentiments and expressions of humanity, from the debasing to the angelic, are parts of a seamless whole, the global conversation of bits. We cannot separate the air that chokes from the air upon which wings beat.\r\n\r\nIn China, Germany, France, Russia, Singapore, Italy and the United States, you are trying to ward off the virus of liberty by erecting guard posts at the frontiers of Cyberspace. These may keep out the contagion for a small time, but they will not work in a world that will soon be blanketed in bit-bearing media.\r\n\r\nYour increasingly obsolete information industries would perpetuate themselves by proposing laws, in America and elsewhere, that claim to own speech itself throughout the world. These laws would declare ideas to be another industrial product, no more noble than pig iron. In our world, whatever the human mind may create can be reproduced and distributed infinitely at no cost. The global conveyance of thought no longer requires your factories to accomplish.\r\n\r\nThese increasingly hostile and colonial measures place us in the same position as those previous lovers of freedom and self-determination who had to reject the authorities of distant, uninformed powers. We must declare our virtual selves immune to your sovereignty, even as we continue to consent to your rule over our bodies. We will spread ourselves across the Planet so that no one can arrest our thoughts.\r\n\r\nWe will create a civilization of the Mind in Cyberspace. May it be more humane and fair than the world your governments have made before.";
         test_sporkmarmot(&data[..]);
     }
+
+    #[test]
+    fn test_interleaved_with_parity_round_trip() {
+        let buf = read_sporkmarmot();
+        let data = b"redundancy protects against cropping";
+        let opts = EncodeOptions {
+            interleave_depth: 1,
+            parity_blocks: 2,
+        };
+        let steg = encode_img_inner_with_options(&buf, data, opts).unwrap();
+        let decoded = decode_img_inner(&steg).unwrap();
+        assert_eq!(&data[..], decoded);
+    }
+
+    #[test]
+    fn test_keyed_round_trip() {
+        let buf = read_sporkmarmot();
+        let data = b"only the key holder should read this";
+        let key = [7u8; 32];
+
+        let steg = encode_img_keyed_inner(&buf, data, &key).unwrap();
+        let decoded = decode_img_keyed_inner(&steg, &key).unwrap();
+        assert_eq!(&data[..], decoded);
+    }
+
+    #[test]
+    fn test_keyed_decode_rejects_wrong_key() {
+        let buf = read_sporkmarmot();
+        let data = b"only the key holder should read this";
+        let key = [7u8; 32];
+        let wrong_key = [8u8; 32];
+
+        let steg = encode_img_keyed_inner(&buf, data, &key).unwrap();
+        assert!(decode_img_keyed_inner(&steg, &wrong_key).is_err());
+        assert!(decode_img_inner(&steg).is_err());
+    }
+
+    #[test]
+    fn test_keyed_placement_round_trip() {
+        let buf = read_sporkmarmot();
+        let data = b"scattered across the whole image, not just the front pixels";
+        let key = b"a secret placement key";
+
+        let steg = encode_img_with_key_inner(&buf, data, key).unwrap();
+        let decoded = decode_img_with_key_inner(&steg, key).unwrap();
+        assert_eq!(&data[..], decoded);
+    }
+
+    #[test]
+    fn test_keyed_placement_rejects_wrong_key() {
+        let buf = read_sporkmarmot();
+        let data = b"scattered across the whole image, not just the front pixels";
+        let key = b"a secret placement key";
+        let wrong_key = b"not the right key at all";
+
+        let steg = encode_img_with_key_inner(&buf, data, key).unwrap();
+        assert!(decode_img_with_key_inner(&steg, wrong_key).is_err());
+    }
+
+    #[test]
+    fn test_decode_rejects_non_stego_image() {
+        let buf = read_sporkmarmot();
+        assert!(decode_img_inner(&buf).is_err());
+    }
+
+    #[test]
+    fn test_decode_survives_damage_to_primary_header_anchor() {
+        let buf = read_sporkmarmot();
+        let data = b"a damaged primary anchor shouldn't doom the decode";
+        let steg = encode_img_inner(&buf, data).unwrap();
+
+        let mut img = image::load_from_memory(&steg).unwrap().into_rgba8();
+        let width = img.width();
+        let header_bits = super::HEADER_BLOCK_LEN * 8;
+        // flip far more bytes than the inner RS code at the top-left anchor
+        // can correct, forcing decode to fall back to a different anchor
+        for i in 0..header_bits {
+            let x = (i as u32) % width;
+            let y = (i as u32) / width;
+            let pixel = img.get_pixel_mut(x, y);
+            let chan = i % 3;
+            pixel[chan] ^= 0x1;
+        }
+
+        let mut damaged = Vec::new();
+        image::DynamicImage::ImageRgba8(img)
+            .write_to(&mut std::io::Cursor::new(&mut damaged), image::ImageFormat::Png)
+            .unwrap();
+
+        let decoded = decode_img_inner(&damaged).unwrap();
+        assert_eq!(&data[..], decoded);
+    }
+
+    #[test]
+    fn test_encode_too_small_image_errs_instead_of_panicking() {
+        // smaller than a single header block's worth of bits: the reserved
+        // header-anchor range alone exceeds the image's total bit capacity
+        let tiny = image::RgbaImage::new(4, 4);
+        let mut buf = Vec::new();
+        image::DynamicImage::ImageRgba8(tiny)
+            .write_to(&mut std::io::Cursor::new(&mut buf), image::ImageFormat::Png)
+            .unwrap();
+
+        assert!(encode_img_inner(&buf, b"too small").is_err());
+    }
+
+    #[test]
+    fn test_outer_parity_rejects_too_many_blocks() {
+        let buf = read_sporkmarmot();
+        // 250 data blocks + 10 parity blocks exceeds the 255-symbol outer
+        // Reed-Solomon codeword limit
+        let data = vec![0u8; 250 * 223];
+        let opts = EncodeOptions {
+            interleave_depth: 0,
+            parity_blocks: 10,
+        };
+        assert!(encode_img_inner_with_options(&buf, &data, opts).is_err());
+    }
+
+    #[test]
+    fn test_outer_parity_recovers_a_completely_lost_block() {
+        let buf = read_sporkmarmot();
+        let data = b"redundancy protects against cropping";
+        let opts = EncodeOptions {
+            interleave_depth: 0,
+            parity_blocks: 2,
+        };
+        let steg = encode_img_inner_with_options(&buf, data, opts).unwrap();
+
+        let mut img = image::load_from_memory(&steg).unwrap().into_rgba8();
+        let width = img.width();
+        let height = img.height();
+        let header_bits = (HEADER_BLOCK_LEN * 8) as u32;
+        let anchors = header_anchor_offsets(width, height);
+        let reserved = merged_reserved_ranges(&anchors, header_bits);
+        let capacity = logical_capacity(width, height, &reserved).unwrap();
+
+        // one data block plus two outer parity blocks were placed
+        // sequentially; blow away the entire first (data) block's region,
+        // as a crop or an opaque overlay would, and confirm outer parity
+        // still reconstructs it.
+        let total_blocks = 3;
+        let block_region_size = capacity / total_blocks;
+        for bit in 0..block_region_size {
+            let i = physical_bit_index(bit, &reserved);
+            let x = i % width;
+            let y = i / width;
+            let pixel = img.get_pixel_mut(x, y);
+            let chan = (i % 3) as usize;
+            pixel[chan] ^= 0x1;
+        }
+
+        let mut damaged = Vec::new();
+        image::DynamicImage::ImageRgba8(img)
+            .write_to(&mut std::io::Cursor::new(&mut damaged), image::ImageFormat::Png)
+            .unwrap();
+
+        let decoded = decode_img_inner(&damaged).unwrap();
+        assert_eq!(&data[..], decoded);
+    }
+
+    #[test]
+    fn test_interleaving_survives_a_contiguous_burst_of_damage() {
+        let buf = read_sporkmarmot();
+        let data = vec![b'x'; 300]; // spans two data blocks: 223 + 77 bytes
+        let opts = EncodeOptions {
+            interleave_depth: 4,
+            parity_blocks: 0,
+        };
+        let steg = encode_img_inner_with_options(&buf, &data, opts).unwrap();
+
+        let mut img = image::load_from_memory(&steg).unwrap().into_rgba8();
+        let width = img.width();
+        let height = img.height();
+        let header_bits = (HEADER_BLOCK_LEN * 8) as u32;
+        let anchors = header_anchor_offsets(width, height);
+        let reserved = merged_reserved_ranges(&anchors, header_bits);
+
+        // interleaving scatters each codeword's bytes depth-slots apart, so a
+        // contiguous run of `depth` slots only ever clips one byte out of each
+        // codeword. Flip 10 contiguous byte-columns (40 slots, so 10 bytes per
+        // codeword: well under the inner RS code's 16-byte correction budget
+        // per codeword), simulating a
+        // scratch or crop that would have wiped out a whole block under
+        // sequential placement.
+        let depth = 4;
+        for byte_idx in 50..60 {
+            for codeword_idx in 0..depth {
+                let slot = byte_idx * depth + codeword_idx;
+                for b in 0..8 {
+                    let i = physical_bit_index((slot as u32) * 8 + b, &reserved);
+                    let x = i % width;
+                    let y = i / width;
+                    let pixel = img.get_pixel_mut(x, y);
+                    let chan = (i % 3) as usize;
+                    pixel[chan] ^= 0x1;
+                }
+            }
+        }
+
+        let mut damaged = Vec::new();
+        image::DynamicImage::ImageRgba8(img)
+            .write_to(&mut std::io::Cursor::new(&mut damaged), image::ImageFormat::Png)
+            .unwrap();
+
+        let decoded = decode_img_inner(&damaged).unwrap();
+        assert_eq!(&data[..], decoded);
+    }
 }